@@ -0,0 +1,101 @@
+// Implements the `sfrs convert --from <backend> --to <backend>` subcommand:
+// streams every item out of a source `ItemStore`, batch by batch in id
+// order, and re-inserts it into a destination `ItemStore`, preserving ids,
+// so operators can migrate between storage engines (e.g. SQLite -> sled)
+// without a client ever noticing.
+
+use crate::item::ItemOpError;
+use crate::store::{ItemStore, SledStore, SqliteStore};
+use diesel::sqlite::SqliteConnection;
+use diesel::Connection;
+
+/// The backends `sfrs convert` knows how to open.
+pub enum Backend {
+    Sqlite,
+    Sled
+}
+
+impl Backend {
+    pub fn parse(s: &str) -> Result<Backend, ItemOpError> {
+        match s {
+            "sqlite" => Ok(Backend::Sqlite),
+            "sled" => Ok(Backend::Sled),
+            other => Err(ItemOpError::new(format!("unknown backend '{}' (expected 'sqlite' or 'sled')", other)))
+        }
+    }
+}
+
+pub struct ConvertArgs {
+    pub from: Backend,
+    pub from_path: String,
+    pub to: Backend,
+    pub to_path: String
+}
+
+// How many items to hold in memory at once while migrating.
+const BATCH_SIZE: i64 = 500;
+
+fn open_sqlite(path: &str) -> Result<SqliteConnection, ItemOpError> {
+    SqliteConnection::establish(path).map_err(|e| ItemOpError::new(e.to_string()))
+}
+
+// Stream `source`'s contents into `dest` in ascending-id batches of
+// `BATCH_SIZE`, instead of loading the whole backend into memory at once.
+// Returns the number of items migrated.
+fn migrate_batches(source: &impl ItemStore, dest: &impl ItemStore) -> Result<usize, ItemOpError> {
+    let mut since_id = None;
+    let mut migrated = 0;
+
+    loop {
+        let batch = source.all_items_ordered(since_id, Some(BATCH_SIZE))?;
+        if batch.is_empty() {
+            break;
+        }
+
+        since_id = batch.last().map(|it| it.id);
+        for item in &batch {
+            dest.insert_raw(item)?;
+        }
+        migrated += batch.len();
+
+        if (batch.len() as i64) < BATCH_SIZE {
+            break;
+        }
+    }
+
+    Ok(migrated)
+}
+
+/// Run a full migration: read every item out of the `from` backend, in id
+/// order, and insert it as-is into the `to` backend. `from_path`/`to_path`
+/// are always used to open their respective backend, regardless of which
+/// backends are involved, so a `--from sqlite --from-path X` invocation
+/// always reads from `X`. Returns the number of items migrated.
+pub fn run(args: ConvertArgs) -> Result<usize, ItemOpError> {
+    if matches!((&args.from, &args.to), (Backend::Sqlite, Backend::Sqlite)) && args.from_path == args.to_path {
+        return Err(ItemOpError::new("source and destination paths must differ"));
+    }
+
+    match (&args.from, &args.to) {
+        (Backend::Sqlite, Backend::Sqlite) => {
+            let source_conn = open_sqlite(&args.from_path)?;
+            let dest_conn = open_sqlite(&args.to_path)?;
+            migrate_batches(&SqliteStore::new(&source_conn), &SqliteStore::new(&dest_conn))
+        }
+        (Backend::Sqlite, Backend::Sled) => {
+            let source_conn = open_sqlite(&args.from_path)?;
+            let dest = SledStore::open(&args.to_path)?;
+            migrate_batches(&SqliteStore::new(&source_conn), &dest)
+        }
+        (Backend::Sled, Backend::Sqlite) => {
+            let source = SledStore::open(&args.from_path)?;
+            let dest_conn = open_sqlite(&args.to_path)?;
+            migrate_batches(&source, &SqliteStore::new(&dest_conn))
+        }
+        (Backend::Sled, Backend::Sled) => {
+            let source = SledStore::open(&args.from_path)?;
+            let dest = SledStore::open(&args.to_path)?;
+            migrate_batches(&source, &dest)
+        }
+    }
+}
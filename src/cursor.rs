@@ -0,0 +1,36 @@
+use crate::item::ItemOpError;
+use serde::{Serialize, Deserialize};
+
+// An opaque pagination token: base64 of `{last_id, max_id}`. Clients treat
+// this as an opaque string; only `items_page` and `items_save_batch` ever
+// look inside it.
+//
+// `max_id`, when present, pins the page to a snapshot of the backlog (the
+// highest id that existed when paging started), so pages keep making sense
+// even if new items arrive while a client works through a large backlog.
+// This is what `cursor_token` carries between calls that are still paging
+// through the same snapshot.
+//
+// `max_id: None` means "incremental sync": the client has caught up to
+// `last_id` and `items_page` should take a fresh snapshot of the current
+// max id rather than reusing an old one, so new items aren't missed. This
+// is what `sync_token` carries, and it's what tells `items_page` apart
+// from a `cursor_token` for the same underlying `last_id`.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Cursor {
+    pub last_id: i64,
+    pub max_id: Option<i64>
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        // Cursor is a plain struct of two integers; it always serializes.
+        let json = serde_json::to_vec(self).expect("Cursor always serializes");
+        base64::encode(json)
+    }
+
+    pub fn decode(token: &str) -> Result<Cursor, ItemOpError> {
+        let bytes = base64::decode(token).map_err(|_| ItemOpError::new("Invalid cursor token"))?;
+        serde_json::from_slice(&bytes).map_err(|_| ItemOpError::new("Invalid cursor token"))
+    }
+}
@@ -1,18 +1,29 @@
-use crate::schema::items;
-use crate::schema::items::dsl::*;
-use crate::{SqliteLike, lock_db_write, lock_db_read};
+use crate::cursor::Cursor;
+use crate::revision::{self, Revision, RevisionMeta};
+use crate::SqliteLike;
+use crate::store::{ItemPage, ItemStore, SaveResult, SqliteStore};
 use crate::user;
-use diesel::dsl::max;
-use diesel::prelude::*;
+use diesel::Queryable;
 use serde::{Serialize, Deserialize};
 use std::vec::Vec;
 
 #[derive(Debug)]
-pub struct ItemOpError(pub String);
+pub enum ItemOpError {
+    // No row matched the lookup. Kept distinct from `Other` so callers
+    // like `ItemStore::items_save_batch` can tell "no existing item" apart
+    // from "the database call failed", which must not be treated as if
+    // the item didn't exist.
+    NotFound,
+    Other(String)
+}
 
 impl ItemOpError {
-    fn new(s: impl Into<String>) -> ItemOpError {
-        ItemOpError(s.into())
+    pub(crate) fn new(s: impl Into<String>) -> ItemOpError {
+        ItemOpError::Other(s.into())
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, ItemOpError::NotFound)
     }
 }
 
@@ -22,7 +33,7 @@ impl Into<ItemOpError> for &str {
     }
 }
 
-#[derive(Queryable)]
+#[derive(Queryable, Clone)]
 pub struct Item {
     // This "id", though primary key, is not how the client actually
     // identifies an item, and it is not sent to the client.
@@ -43,19 +54,6 @@ pub struct Item {
     pub updated_at: Option<String>
 }
 
-#[derive(Insertable)]
-#[table_name = "items"]
-struct InsertItem {
-    owner: i32,
-    uuid: String,
-    content: Option<String>,
-    content_type: String,
-    enc_item_key: Option<String>,
-    deleted: bool,
-    created_at: String,
-    updated_at: Option<String>
-}
-
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SyncItem {
     pub uuid: String,
@@ -83,39 +81,21 @@ impl Into<SyncItem> for Item {
 }
 
 impl SyncItem {
+    // These are thin wrappers around `SqliteStore`, the default `ItemStore`
+    // implementation, kept so existing callers that only know about
+    // `SqliteLike` connections don't need to change. New code that wants a
+    // specific backend (or to work against any `ItemStore`) should go
+    // through `store::ItemStore` directly.
     pub fn items_of_user(
         db: &impl SqliteLike, u: &user::User,
         since_id: Option<i64>, max_id: Option<i64>,
         limit: Option<i64>
     ) -> Result<Vec<Item>, ItemOpError> {
-        lock_db_read!()
-            .and_then(|_| {
-                let mut stmt = items.filter(owner.eq(u.id)).into_boxed();
-                if let Some(limit) = limit {
-                    stmt = stmt.limit(limit);
-                }
-
-                if let Some(since_id) = since_id {
-                    stmt = stmt.filter(id.gt(since_id));
-                }
-
-                if let Some(max_id) = max_id {
-                    stmt = stmt.filter(id.le(max_id));
-                }
-
-                stmt.order(id.asc())
-                    .load::<Item>(db)
-                    .map_err(|_| "Database error".into())
-            })
+        SqliteStore::new(db).items_of_user(u, since_id, max_id, limit)
     }
 
     pub fn find_item_by_uuid(db: &impl SqliteLike, u: &user::User, i: &str) -> Result<Item, ItemOpError> {
-        lock_db_read!()
-            .and_then(|_| {
-                items.filter(owner.eq(u.id).and(uuid.eq(i)))
-                    .first::<Item>(db)
-                    .map_err(|_| "Database error".into())
-            })
+        SqliteStore::new(db).find_item_by_uuid(u, i)
     }
 
     // Get the current maximum item ID for a user.
@@ -123,50 +103,40 @@ impl SyncItem {
     // every time an item is updated (see Self::items_insert).
     // The ID returned by this function is more like a "timestamp" of the latest "state"
     pub fn get_current_max_id(db: &impl SqliteLike, u: &user::User) -> Result<Option<i64>, ItemOpError> {
-        lock_db_read!()
-            .and_then(|_| {
-                items.filter(owner.eq(u.id))
-                    .select(max(id))
-                    .first::<Option<i64>>(db)
-                    .map_err(|_| "Database error".into())
-            })
+        SqliteStore::new(db).get_current_max_id(u)
     }
 
     pub fn items_insert(db: &impl SqliteLike, u: &user::User, it: &SyncItem) -> Result<i64, ItemOpError> {
-        // First, try to find the original item, if any, delete it, and insert a new one with the same UUID
-        // This way, the ID is updated each time an item is updated
-        // This method acts both as insertion and update
-        let orig = lock_db_read!()
-            .and_then(|_| {
-                items.filter(uuid.eq(&it.uuid).and(owner.eq(u.id)))
-                    .load::<Item>(db)
-                    .map_err(|_| "Database error".into())
-            })?;
+        SqliteStore::new(db).items_insert(u, it)
+    }
 
-        let _lock = lock_db_write!()?;
-        if !orig.is_empty() {
-            diesel::delete(items.filter(uuid.eq(&it.uuid).and(owner.eq(u.id))))
-                .execute(db)
-                .map(|_| ())
-                .map_err(|_| "Database error".into())?;
-        }
+    /// Save a batch of items from a sync request, rejecting any that
+    /// conflict with a newer, diverged server copy. `sync_token` is the
+    /// opaque token the client last got back from `items_page`. See
+    /// `store::ItemStore::items_save_batch` for the conflict rule.
+    pub fn items_save_batch(
+        db: &impl SqliteLike, u: &user::User,
+        sync_token: Option<&str>, incoming: &[SyncItem]
+    ) -> Result<SaveResult, ItemOpError> {
+        SqliteStore::new(db).items_save_batch(u, sync_token, incoming)
+    }
 
-        diesel::insert_into(items::table)
-            .values(InsertItem {
-                owner: u.id,
-                uuid: it.uuid.clone(),
-                content: if it.deleted { None } else { it.content.clone() },
-                content_type: it.content_type.clone(),
-                enc_item_key: if it.deleted { None } else { it.enc_item_key.clone() },
-                deleted: it.deleted,
-                created_at: it.created_at.clone(),
-                updated_at: it.updated_at.clone()
-            })
-            .execute(db)
-            .map_err(|_| "Database error".into())?;
-        std::mem::drop(_lock);
+    /// List the revisions kept for an item, most recent last.
+    pub fn revisions_of_item(db: &impl SqliteLike, u: &user::User, item_uuid: &str) -> Result<Vec<RevisionMeta>, ItemOpError> {
+        revision::revisions_of_item(db, u, item_uuid)
+    }
+
+    /// Fetch the full stored payload of one of an item's past revisions.
+    pub fn get_revision(db: &impl SqliteLike, u: &user::User, item_uuid: &str, revision_id: i64) -> Result<Revision, ItemOpError> {
+        revision::get_revision(db, u, item_uuid, revision_id)
+    }
 
-        Self::find_item_by_uuid(db, u, &it.uuid)
-            .map(|i| i.id)
+    /// Fetch one page of a user's items by opaque cursor rather than raw
+    /// ids. See `store::ItemStore::items_page` for the token semantics.
+    pub fn items_page(
+        db: &impl SqliteLike, u: &user::User,
+        cursor: Option<Cursor>, limit: i64
+    ) -> Result<ItemPage, ItemOpError> {
+        SqliteStore::new(db).items_page(u, cursor, limit)
     }
 }
\ No newline at end of file
@@ -0,0 +1,92 @@
+use crate::item::{Item, ItemOpError};
+use crate::schema::item_revisions;
+use crate::schema::item_revisions::dsl::*;
+use crate::user;
+use crate::{lock_db_read, SqliteLike};
+use diesel::prelude::*;
+use serde::Serialize;
+
+// A past version of an item's content, captured by `record` every time
+// `SyncItem::items_insert` is about to replace the current row. Unlike
+// `item::Item`, a revision's own `id` is just its row number; `item_id` is
+// the id the *item* had at the moment this snapshot was taken.
+#[derive(Queryable)]
+pub struct Revision {
+    pub id: i64,
+    pub owner: i32,
+    pub uuid: String,
+    pub content: Option<String>,
+    pub content_type: String,
+    pub enc_item_key: Option<String>,
+    pub created_at: String,
+    pub item_id: i64
+}
+
+#[derive(Insertable)]
+#[table_name = "item_revisions"]
+struct InsertRevision {
+    owner: i32,
+    uuid: String,
+    content: Option<String>,
+    content_type: String,
+    enc_item_key: Option<String>,
+    created_at: String,
+    item_id: i64
+}
+
+#[derive(Serialize)]
+pub struct RevisionMeta {
+    pub id: i64,
+    pub item_id: i64,
+    pub created_at: String
+}
+
+impl From<&Revision> for RevisionMeta {
+    fn from(r: &Revision) -> RevisionMeta {
+        RevisionMeta { id: r.id, item_id: r.item_id, created_at: r.created_at.clone() }
+    }
+}
+
+// Append `prev` (an item's content just before it gets overwritten) as a
+// new revision. Called from `items_insert` before the old row is deleted.
+pub(crate) fn record(db: &impl SqliteLike, prev: &Item) -> Result<(), ItemOpError> {
+    diesel::insert_into(item_revisions::table)
+        .values(InsertRevision {
+            owner: prev.owner,
+            uuid: prev.uuid.clone(),
+            content: prev.content.clone(),
+            content_type: prev.content_type.clone(),
+            enc_item_key: prev.enc_item_key.clone(),
+            // `prev.created_at` never changes across updates, so every
+            // revision of an item would otherwise carry the same
+            // timestamp. `updated_at` is this version's own timestamp
+            // (falling back to `created_at` for an item that was never
+            // updated), which is what clients need to order/display
+            // history.
+            created_at: prev.updated_at.clone().unwrap_or_else(|| prev.created_at.clone()),
+            item_id: prev.id
+        })
+        .execute(db)
+        .map(|_| ())
+        .map_err(|_| "Database error".into())
+}
+
+pub fn revisions_of_item(db: &impl SqliteLike, u: &user::User, item_uuid: &str) -> Result<Vec<RevisionMeta>, ItemOpError> {
+    lock_db_read!()
+        .and_then(|_| {
+            item_revisions.filter(owner.eq(u.id).and(uuid.eq(item_uuid)))
+                .order(id.asc())
+                .load::<Revision>(db)
+                .map_err(|_| "Database error".into())
+        })
+        .map(|revs| revs.iter().map(RevisionMeta::from).collect())
+}
+
+pub fn get_revision(db: &impl SqliteLike, u: &user::User, item_uuid: &str, revision_id: i64) -> Result<Revision, ItemOpError> {
+    lock_db_read!()
+        .and_then(|_| {
+            item_revisions.filter(owner.eq(u.id).and(uuid.eq(item_uuid)).and(id.eq(revision_id)))
+                .first::<Revision>(db)
+                .map_err(|_| "Database error".into())
+        })
+}
@@ -0,0 +1,208 @@
+use super::ItemStore;
+use crate::item::{Item, SyncItem, ItemOpError};
+use crate::user::User;
+use serde::{Serialize, Deserialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::RwLock;
+
+// Mirrors `item::Item`, but owns its fields so it can be bincode-encoded
+// independently of the Diesel `Queryable` representation.
+#[derive(Serialize, Deserialize)]
+struct StoredItem {
+    id: i64,
+    owner: i32,
+    uuid: String,
+    content: Option<String>,
+    content_type: String,
+    enc_item_key: Option<String>,
+    deleted: bool,
+    created_at: String,
+    updated_at: Option<String>
+}
+
+impl From<StoredItem> for Item {
+    fn from(s: StoredItem) -> Item {
+        Item {
+            id: s.id,
+            owner: s.owner,
+            uuid: s.uuid,
+            content: s.content,
+            content_type: s.content_type,
+            enc_item_key: s.enc_item_key,
+            deleted: s.deleted,
+            created_at: s.created_at,
+            updated_at: s.updated_at
+        }
+    }
+}
+
+/// An embedded key-value `ItemStore`, backed by `sled`.
+///
+/// Items are keyed by `(owner, uuid)` so a single lookup finds a user's
+/// copy of an item directly, and a secondary `maxid:<owner>` key tracks
+/// the highest id that owner's items have reached, the same way
+/// `AUTOINCREMENT` plus a `WHERE owner = ?` scan does for the SQLite
+/// backend. Like the SQLite backend, the id itself is a single counter
+/// shared by every owner, not a per-owner sequence.
+pub struct SledStore {
+    db: sled::Db,
+    // Per-open-handle cache of the next id to hand out; sled itself is the
+    // source of truth (see `open`), this just avoids a full scan before
+    // every insert.
+    next_id: AtomicI64,
+    // `SqliteStore` gets "one write at a time, readers don't block each
+    // other" from `lock_db_read!`/`lock_db_write!` around the shared
+    // connection. `SledStore` has no such shared connection to hang a lock
+    // off of, but an item insert is still two separate sled writes (the
+    // row, then `maxid:<owner>`) that need to land as a unit, so the same
+    // discipline is reproduced locally with a plain `RwLock`.
+    write_lock: RwLock<()>
+}
+
+impl SledStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ItemOpError> {
+        let db = sled::open(path).map_err(|e| ItemOpError::new(e.to_string()))?;
+        let next_id = db.iter()
+            .filter_map(|kv| kv.ok())
+            .filter(|(k, _)| k.starts_with(b"item:"))
+            .filter_map(|(_, v)| bincode::deserialize::<StoredItem>(&v).ok())
+            .map(|it| it.id)
+            .max()
+            .unwrap_or(0);
+        Ok(SledStore { db, next_id: AtomicI64::new(next_id), write_lock: RwLock::new(()) })
+    }
+
+    fn item_key(owner: i32, item_uuid: &str) -> Vec<u8> {
+        format!("item:{}:{}", owner, item_uuid).into_bytes()
+    }
+
+    fn max_id_key(owner: i32) -> Vec<u8> {
+        format!("maxid:{}", owner).into_bytes()
+    }
+
+    // Raise `maxid:<owner>` to `candidate` if it's higher, atomically.
+    // `update_and_fetch` retries its closure against sled's CAS primitive
+    // until it applies cleanly, so two concurrent inserts for the same
+    // owner can't both read the old value and have the lower one win.
+    fn bump_max_id(&self, owner: i32, candidate: i64) -> Result<(), ItemOpError> {
+        self.db.update_and_fetch(Self::max_id_key(owner), |old| {
+            let current = old
+                .and_then(|v| v.try_into().ok().map(i64::from_be_bytes))
+                .unwrap_or(0);
+            Some(candidate.max(current).to_be_bytes().to_vec())
+        }).map_err(|e| ItemOpError::new(e.to_string()))?;
+        Ok(())
+    }
+}
+
+impl ItemStore for SledStore {
+    fn items_of_user(
+        &self, u: &User,
+        since_id: Option<i64>, max_id: Option<i64>, limit: Option<i64>
+    ) -> Result<Vec<Item>, ItemOpError> {
+        let _guard = self.write_lock.read().map_err(|_| ItemOpError::new("lock poisoned"))?;
+        let prefix = format!("item:{}:", u.id);
+        let mut out: Vec<Item> = self.db.scan_prefix(prefix.as_bytes())
+            .filter_map(|kv| kv.ok())
+            .filter_map(|(_, v)| bincode::deserialize::<StoredItem>(&v).ok())
+            .map(Item::from)
+            .filter(|it| since_id.map_or(true, |s| it.id > s))
+            .filter(|it| max_id.map_or(true, |m| it.id <= m))
+            .collect();
+
+        out.sort_by_key(|it| it.id);
+        if let Some(limit) = limit {
+            out.truncate(limit.max(0) as usize);
+        }
+        Ok(out)
+    }
+
+    fn find_item_by_uuid(&self, u: &User, item_uuid: &str) -> Result<Item, ItemOpError> {
+        let _guard = self.write_lock.read().map_err(|_| ItemOpError::new("lock poisoned"))?;
+        self.db.get(Self::item_key(u.id, item_uuid))
+            .map_err(|e| ItemOpError::new(e.to_string()))?
+            .ok_or(ItemOpError::NotFound)
+            .and_then(|v| bincode::deserialize::<StoredItem>(&v).map_err(|e| ItemOpError::new(e.to_string())))
+            .map(Item::from)
+    }
+
+    fn get_current_max_id(&self, u: &User) -> Result<Option<i64>, ItemOpError> {
+        let _guard = self.write_lock.read().map_err(|_| ItemOpError::new("lock poisoned"))?;
+        self.db.get(Self::max_id_key(u.id))
+            .map_err(|e| ItemOpError::new(e.to_string()))
+            .map(|v| v.and_then(|v| v.as_ref().try_into().ok().map(i64::from_be_bytes)))
+    }
+
+    // Unlike `SqliteStore::items_insert`, this does not capture the
+    // overwritten row as a revision first: revision history (`item_revisions`)
+    // is Diesel/SQLite-only for now (see `ItemStore::items_insert`), so
+    // updates made through this backend do not retain prior versions.
+    //
+    // The row write and the `maxid:<owner>` bump below are two separate
+    // sled writes; holding `write_lock` across both keeps a concurrent
+    // reader from ever observing the new row with the old max (which
+    // `items_page` would otherwise read as "item not in this snapshot yet"
+    // and skip).
+    fn items_insert(&self, u: &User, it: &SyncItem) -> Result<i64, ItemOpError> {
+        let _guard = self.write_lock.write().map_err(|_| ItemOpError::new("lock poisoned"))?;
+        let new_id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let stored = StoredItem {
+            id: new_id,
+            owner: u.id,
+            uuid: it.uuid.clone(),
+            content: if it.deleted { None } else { it.content.clone() },
+            content_type: it.content_type.clone(),
+            enc_item_key: if it.deleted { None } else { it.enc_item_key.clone() },
+            deleted: it.deleted,
+            created_at: it.created_at.clone(),
+            updated_at: it.updated_at.clone()
+        };
+
+        let encoded = bincode::serialize(&stored).map_err(|e| ItemOpError::new(e.to_string()))?;
+        self.db.insert(Self::item_key(u.id, &it.uuid), encoded)
+            .map_err(|e| ItemOpError::new(e.to_string()))?;
+        self.bump_max_id(u.id, new_id)?;
+
+        Ok(new_id)
+    }
+
+    fn all_items_ordered(&self, since_id: Option<i64>, limit: Option<i64>) -> Result<Vec<Item>, ItemOpError> {
+        let _guard = self.write_lock.read().map_err(|_| ItemOpError::new("lock poisoned"))?;
+        let mut out: Vec<Item> = self.db.scan_prefix(b"item:")
+            .filter_map(|kv| kv.ok())
+            .filter_map(|(_, v)| bincode::deserialize::<StoredItem>(&v).ok())
+            .map(Item::from)
+            .filter(|it| since_id.map_or(true, |s| it.id > s))
+            .collect();
+        out.sort_by_key(|it| it.id);
+        if let Some(limit) = limit {
+            out.truncate(limit.max(0) as usize);
+        }
+        Ok(out)
+    }
+
+    fn insert_raw(&self, it: &Item) -> Result<(), ItemOpError> {
+        let _guard = self.write_lock.write().map_err(|_| ItemOpError::new("lock poisoned"))?;
+        let stored = StoredItem {
+            id: it.id,
+            owner: it.owner,
+            uuid: it.uuid.clone(),
+            content: it.content.clone(),
+            content_type: it.content_type.clone(),
+            enc_item_key: it.enc_item_key.clone(),
+            deleted: it.deleted,
+            created_at: it.created_at.clone(),
+            updated_at: it.updated_at.clone()
+        };
+
+        let encoded = bincode::serialize(&stored).map_err(|e| ItemOpError::new(e.to_string()))?;
+        self.db.insert(Self::item_key(it.owner, &it.uuid), encoded)
+            .map_err(|e| ItemOpError::new(e.to_string()))?;
+        self.bump_max_id(it.owner, it.id)?;
+        self.next_id.fetch_max(it.id, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+pub mod sqlite;
+pub mod kv;
+
+use crate::cursor::Cursor;
+use crate::item::{Item, SyncItem, ItemOpError};
+use crate::user::User;
+use serde::Serialize;
+
+pub use sqlite::SqliteStore;
+pub use kv::SledStore;
+
+/// An item the sync endpoint refused to overwrite because the server's
+/// copy is newer than the `sync_token` the client sent and the two have
+/// diverged. Sent back to the client so it can fork its copy into a
+/// duplicate, the same way Standard Notes' `sync_conflict` items work.
+#[derive(Serialize, Clone)]
+pub struct SyncConflict {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub unsaved_item: SyncItem,
+    pub server_item: SyncItem
+}
+
+/// Result of saving a batch of items during a sync: the items that were
+/// written, and the items that were rejected as conflicts instead.
+pub struct SaveResult {
+    pub saved: Vec<Item>,
+    pub conflicts: Vec<SyncConflict>
+}
+
+/// One page of a user's items, plus opaque tokens for what to fetch next.
+///
+/// At most one of the two tokens is ever set: `cursor_token` when there's
+/// more of the current backlog snapshot left to page through, or
+/// `sync_token` once the client has caught up to that snapshot and should
+/// switch back to incremental syncing.
+pub struct ItemPage {
+    pub items: Vec<Item>,
+    pub sync_token: Option<String>,
+    pub cursor_token: Option<String>
+}
+
+/// Backend-agnostic storage operations for synced items.
+///
+/// `SyncItem`'s public API used to talk to Diesel/SQLite directly; it now
+/// goes through this trait instead, so a deployment can swap in an
+/// embedded key-value engine (see `kv::SledStore`) without touching the
+/// sync logic. Implementations are expected to provide the same
+/// semantics as the original SQLite-backed methods on `SyncItem`.
+pub trait ItemStore {
+    /// List a user's items, oldest-to-newest, optionally bounded by
+    /// `since_id` (exclusive), `max_id` (inclusive) and `limit`.
+    fn items_of_user(
+        &self, u: &User,
+        since_id: Option<i64>, max_id: Option<i64>, limit: Option<i64>
+    ) -> Result<Vec<Item>, ItemOpError>;
+
+    fn find_item_by_uuid(&self, u: &User, item_uuid: &str) -> Result<Item, ItemOpError>;
+
+    fn get_current_max_id(&self, u: &User) -> Result<Option<i64>, ItemOpError>;
+
+    /// Upsert `it`, allocating it a new id.
+    ///
+    /// Revision history (see `revision::record`) is currently captured
+    /// only by `SqliteStore`, via `item_revisions`, a Diesel/SQLite-only
+    /// table. Other `ItemStore` implementations (e.g. `SledStore`) do not
+    /// retain the overwritten content; `SyncItem::revisions_of_item` and
+    /// `get_revision` only ever read history recorded this way.
+    fn items_insert(&self, u: &User, it: &SyncItem) -> Result<i64, ItemOpError>;
+
+    /// Items across every user, in ascending id order, optionally starting
+    /// after `since_id` and bounded by `limit`. Used by the `convert` CLI
+    /// to stream a backend's contents out batch by batch rather than
+    /// loading everything into memory at once.
+    fn all_items_ordered(&self, since_id: Option<i64>, limit: Option<i64>) -> Result<Vec<Item>, ItemOpError>;
+
+    /// Insert `it` as-is, preserving its id and owner rather than
+    /// allocating a new one. Only meant for the `convert` CLI: migrating
+    /// a backend's contents should reproduce the source exactly, not run
+    /// it back through the normal update-bumps-the-id path.
+    fn insert_raw(&self, it: &Item) -> Result<(), ItemOpError>;
+
+    /// Save a batch of items from a client sync request, honoring
+    /// `sync_token` (the opaque token the client last received from
+    /// `items_page`, marking the highest item id it has already seen).
+    ///
+    /// `sync_token` is decoded with `Cursor::decode`, the inverse of the
+    /// `Cursor::encode` that `items_page` produces its tokens with, so a
+    /// client can round-trip the token it was handed without needing to
+    /// know it encodes a raw id.
+    ///
+    /// For each incoming item, if the server's stored copy has an id
+    /// greater than `sync_token` *and* its content/`updated_at` differ
+    /// from what the client sent, the server's copy is newer than
+    /// anything the client has seen and the two have diverged: that item
+    /// is reported back as a conflict instead of being overwritten.
+    /// Everything else is saved normally.
+    ///
+    /// This only depends on the other `ItemStore` methods, so backends
+    /// get it for free and don't need to reimplement the conflict check.
+    fn items_save_batch(
+        &self, u: &User, sync_token: Option<&str>, incoming: &[SyncItem]
+    ) -> Result<SaveResult, ItemOpError> {
+        let token = sync_token.map(Cursor::decode).transpose()?.map_or(0, |c| c.last_id);
+        let mut saved = Vec::new();
+        let mut conflicts = Vec::new();
+
+        for it in incoming {
+            let existing = match self.find_item_by_uuid(u, &it.uuid) {
+                Ok(existing) => Some(existing),
+                Err(e) if e.is_not_found() => None,
+                // A real lookup failure is not the same as "no existing
+                // item" and must not fall through to an unconditional
+                // overwrite below.
+                Err(e) => return Err(e)
+            };
+
+            let conflict = existing.as_ref().filter(|existing| {
+                existing.id > token
+                    && (existing.content != it.content || existing.updated_at != it.updated_at)
+            });
+
+            match conflict {
+                Some(existing) => conflicts.push(SyncConflict {
+                    kind: "sync_conflict",
+                    unsaved_item: it.clone(),
+                    server_item: existing.clone().into()
+                }),
+                None => {
+                    self.items_insert(u, it)?;
+                    saved.push(self.find_item_by_uuid(u, &it.uuid)?);
+                }
+            }
+        }
+
+        Ok(SaveResult { saved, conflicts })
+    }
+
+    /// Fetch one page of a user's items using an opaque `Cursor` instead of
+    /// raw ids: `cursor` is `None` for the first page of a sync, or the
+    /// `sync_token`/`cursor_token` from a previous call.
+    ///
+    /// A cursor with `max_id: None` (a `sync_token`, or no cursor at all)
+    /// takes a fresh snapshot of the user's current max id, so items
+    /// created since the last sync aren't missed. A cursor with
+    /// `max_id: Some(..)` (a `cursor_token`) continues paging the same
+    /// snapshot from where it left off. Once a page comes back short of
+    /// `limit`, the snapshot is exhausted and the returned `sync_token`
+    /// covers the whole thing, ready for the next incremental sync.
+    fn items_page(&self, u: &User, cursor: Option<Cursor>, limit: i64) -> Result<ItemPage, ItemOpError> {
+        let since_id = cursor.map(|c| c.last_id);
+        let pinned_max_id = cursor.and_then(|c| c.max_id);
+
+        let max_id = match pinned_max_id {
+            Some(max_id) => max_id,
+            None => match self.get_current_max_id(u)? {
+                Some(max_id) => max_id,
+                None => return Ok(ItemPage { items: Vec::new(), sync_token: None, cursor_token: None })
+            }
+        };
+
+        let items = self.items_of_user(u, since_id, Some(max_id), Some(limit))?;
+        let last_id = items.last().map(|i| i.id).or(since_id).unwrap_or(0);
+        let reached_end = (items.len() as i64) < limit;
+
+        let (sync_token, cursor_token) = if reached_end {
+            (Some(Cursor { last_id: max_id, max_id: None }.encode()), None)
+        } else {
+            (None, Some(Cursor { last_id, max_id: Some(max_id) }.encode()))
+        };
+
+        Ok(ItemPage { items, sync_token, cursor_token })
+    }
+}
@@ -0,0 +1,176 @@
+use super::ItemStore;
+use crate::item::{Item, SyncItem, ItemOpError};
+use crate::schema::items;
+use crate::schema::items::dsl::*;
+use crate::{SqliteLike, lock_db_read, lock_db_write};
+use crate::user::User;
+use diesel::dsl::max;
+use diesel::prelude::*;
+
+#[derive(Insertable)]
+#[table_name = "items"]
+struct InsertItem {
+    owner: i32,
+    uuid: String,
+    content: Option<String>,
+    content_type: String,
+    enc_item_key: Option<String>,
+    deleted: bool,
+    created_at: String,
+    updated_at: Option<String>
+}
+
+#[derive(Insertable)]
+#[table_name = "items"]
+struct InsertItemWithId {
+    id: i64,
+    owner: i32,
+    uuid: String,
+    content: Option<String>,
+    content_type: String,
+    enc_item_key: Option<String>,
+    deleted: bool,
+    created_at: String,
+    updated_at: Option<String>
+}
+
+/// The original Diesel/SQLite-backed `ItemStore`. This is the same
+/// storage logic `SyncItem` used to implement directly; it now lives
+/// behind the `ItemStore` trait so it can sit alongside other backends.
+pub struct SqliteStore<'a, T: SqliteLike> {
+    db: &'a T
+}
+
+impl<'a, T: SqliteLike> SqliteStore<'a, T> {
+    pub fn new(db: &'a T) -> Self {
+        SqliteStore { db }
+    }
+}
+
+impl<'a, T: SqliteLike> ItemStore for SqliteStore<'a, T> {
+    fn items_of_user(
+        &self, u: &User,
+        since_id: Option<i64>, max_id: Option<i64>, limit: Option<i64>
+    ) -> Result<Vec<Item>, ItemOpError> {
+        lock_db_read!()
+            .and_then(|_| {
+                let mut stmt = items.filter(owner.eq(u.id)).into_boxed();
+                if let Some(limit) = limit {
+                    stmt = stmt.limit(limit);
+                }
+
+                if let Some(since_id) = since_id {
+                    stmt = stmt.filter(id.gt(since_id));
+                }
+
+                if let Some(max_id) = max_id {
+                    stmt = stmt.filter(id.le(max_id));
+                }
+
+                stmt.order(id.asc())
+                    .load::<Item>(self.db)
+                    .map_err(|_| "Database error".into())
+            })
+    }
+
+    fn find_item_by_uuid(&self, u: &User, item_uuid: &str) -> Result<Item, ItemOpError> {
+        lock_db_read!()
+            .and_then(|_| {
+                items.filter(owner.eq(u.id).and(uuid.eq(item_uuid)))
+                    .first::<Item>(self.db)
+                    .map_err(|e| match e {
+                        diesel::result::Error::NotFound => ItemOpError::NotFound,
+                        _ => ItemOpError::new("Database error")
+                    })
+            })
+    }
+
+    fn get_current_max_id(&self, u: &User) -> Result<Option<i64>, ItemOpError> {
+        lock_db_read!()
+            .and_then(|_| {
+                items.filter(owner.eq(u.id))
+                    .select(max(id))
+                    .first::<Option<i64>>(self.db)
+                    .map_err(|_| "Database error".into())
+            })
+    }
+
+    fn items_insert(&self, u: &User, it: &SyncItem) -> Result<i64, ItemOpError> {
+        // First, try to find the original item, if any, delete it, and insert a new one with the same UUID
+        // This way, the ID is updated each time an item is updated
+        // This method acts both as insertion and update
+        //
+        // The load, the delete, the insert and the re-read all happen inside
+        // one transaction, so another request can't observe or mutate the
+        // same uuid halfway through, and a failure partway through rolls
+        // everything back instead of leaving the item deleted.
+        let _lock = lock_db_write!()?;
+        self.db.transaction(|| {
+            let orig = items.filter(uuid.eq(&it.uuid).and(owner.eq(u.id)))
+                .load::<Item>(self.db)?;
+
+            if let Some(prev) = orig.first() {
+                // Keep the content we're about to overwrite instead of
+                // discarding it, so clients can offer "note history".
+                crate::revision::record(self.db, prev)
+                    .map_err(|_| diesel::result::Error::RollbackTransaction)?;
+
+                diesel::delete(items.filter(uuid.eq(&it.uuid).and(owner.eq(u.id))))
+                    .execute(self.db)?;
+            }
+
+            diesel::insert_into(items::table)
+                .values(InsertItem {
+                    owner: u.id,
+                    uuid: it.uuid.clone(),
+                    content: if it.deleted { None } else { it.content.clone() },
+                    content_type: it.content_type.clone(),
+                    enc_item_key: if it.deleted { None } else { it.enc_item_key.clone() },
+                    deleted: it.deleted,
+                    created_at: it.created_at.clone(),
+                    updated_at: it.updated_at.clone()
+                })
+                .execute(self.db)?;
+
+            items.filter(uuid.eq(&it.uuid).and(owner.eq(u.id)))
+                .first::<Item>(self.db)
+                .map(|i| i.id)
+        }).map_err(|_| "Database error".into())
+    }
+
+    fn all_items_ordered(&self, since_id: Option<i64>, limit: Option<i64>) -> Result<Vec<Item>, ItemOpError> {
+        lock_db_read!()
+            .and_then(|_| {
+                let mut stmt = items.into_boxed();
+                if let Some(since_id) = since_id {
+                    stmt = stmt.filter(id.gt(since_id));
+                }
+                if let Some(limit) = limit {
+                    stmt = stmt.limit(limit);
+                }
+
+                stmt.order(id.asc())
+                    .load::<Item>(self.db)
+                    .map_err(|_| "Database error".into())
+            })
+    }
+
+    fn insert_raw(&self, it: &Item) -> Result<(), ItemOpError> {
+        let _lock = lock_db_write!()?;
+        diesel::insert_into(items::table)
+            .values(InsertItemWithId {
+                id: it.id,
+                owner: it.owner,
+                uuid: it.uuid.clone(),
+                content: it.content.clone(),
+                content_type: it.content_type.clone(),
+                enc_item_key: it.enc_item_key.clone(),
+                deleted: it.deleted,
+                created_at: it.created_at.clone(),
+                updated_at: it.updated_at.clone()
+            })
+            .execute(self.db)
+            .map(|_| ())
+            .map_err(|_| "Database error".into())
+    }
+}